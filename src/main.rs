@@ -1,61 +1,235 @@
+use std::path::PathBuf;
+
+use clap::{Parser, Subcommand};
+
 use turing::TuringMachine;
 
 pub mod turing;
 
-fn main() {
-    let args: Vec<String> = std::env::args().collect();
-    if args.len() == 1 {
-        println!("Please type a command.");
-        return;
+#[derive(Parser)]
+#[command(name = "turing", version, about = "A Turing machine interpreter and composer")]
+struct Cli {
+    #[command(subcommand)]
+    command: Command,
+}
+
+#[derive(Subcommand)]
+enum Command {
+    /// Run a machine on an input tape
+    Compute {
+        /// Path to the machine definition file
+        machine: PathBuf,
+        /// Whitespace-separated input symbols
+        input: String,
+        /// Stop and report "DID NOT HALT" after this many steps
+        #[arg(long)]
+        max_steps: Option<usize>,
+        /// Print only the final accept/reject line instead of every tape snapshot
+        #[arg(long)]
+        quiet: bool,
+        /// Width of the printed tape window
+        #[arg(long, default_value_t = 80, value_parser = parse_tape_width)]
+        tape_width: i64,
+        /// Alias for --quiet
+        #[arg(long)]
+        final_only: bool,
+        /// Stop and report "LOOPS (nonhalting)" if a configuration repeats, instead of
+        /// running forever
+        #[arg(long)]
+        detect_loops: bool,
+        /// Treat `machine` as a `.tmc` file compiled by `compile`, instead of source
+        #[arg(long)]
+        compiled: bool,
+    },
+    /// Feed the output of `machine1` as input to `machine2`
+    Chain {
+        machine1: PathBuf,
+        machine2: PathBuf,
+        outpath: PathBuf,
+    },
+    /// Branch into one of several machines depending on the symbol under the head
+    Branch {
+        entry: PathBuf,
+        /// Whitespace-separated symbols, one per branch machine
+        syms: String,
+        /// Whitespace-separated paths to the branch machines
+        machines: String,
+        outpath: PathBuf,
+    },
+    /// Loop a machine back to its start while the head reads one of `loop_syms`
+    Loop {
+        entry: PathBuf,
+        /// Whitespace-separated symbols that trigger looping back to `entry`
+        loop_syms: String,
+        outpath: PathBuf,
+    },
+    /// Compile a machine's transition table into a `.tmc` bytecode file
+    Compile {
+        machine: PathBuf,
+        outpath: PathBuf,
+    },
+    /// Run the `INPUT => ACCEPT`/`INPUT => REJECT` cases embedded in a machine (or its
+    /// sibling `.test` file) and report a pass/fail summary
+    Test {
+        machine: PathBuf,
+        /// Count a case as failed after this many steps without halting
+        #[arg(long, default_value_t = 10_000)]
+        max_steps: usize,
+    },
+    /// Print a human-readable transition listing for a compiled `.tmc` file
+    Disasm {
+        path: PathBuf,
+    },
+    /// Run a machine as nondeterministic, exploring every rule defined for a
+    /// (state, symbol) pair breadth-first, and accept if any path halts
+    Nondet {
+        machine: PathBuf,
+        /// Whitespace-separated input symbols
+        input: String,
+        /// Give up with an "UNKNOWN" verdict after this many steps
+        #[arg(long, default_value_t = 1_000)]
+        max_depth: usize,
+        /// Give up with an "UNKNOWN" verdict once more than this many configurations are alive at once
+        #[arg(long, default_value_t = 100_000)]
+        max_frontier: usize,
+    },
+}
+
+/// Validates `--tape-width`: it's used as `width / 2` on both sides of tape cell 0 in
+/// [`turing::TuringMachine::get_string`], whose marker clamp panics on a non-positive
+/// width instead of failing cleanly.
+fn parse_tape_width(s: &str) -> Result<i64, String> {
+    let width: i64 = s.parse().map_err(|_| format!("'{}' is not a valid integer", s))?;
+    if width <= 0 {
+        return Err(format!("tape width must be positive, got {}", width));
     }
-    match args[1].as_str() {
-        "compute" => {
-            if args.len() < 4 {
-                eprintln!("Too few arguments! Expected usage: compute [MACHINEPATH] [INPUT]");
-                return;
+    Ok(width)
+}
+
+fn main() {
+    let cli = Cli::parse();
+
+    match cli.command {
+        Command::Compute { machine, input, max_steps, quiet, tape_width, final_only, detect_loops, compiled } => {
+            run_compute(machine, input, max_steps, quiet || final_only, tape_width, detect_loops, compiled);
+        }
+        Command::Chain { machine1, machine2, outpath } => {
+            if let Err(e) = turing::chain(&machine1, &[machine2], &outpath) {
+                eprintln!("{}", e);
+                std::process::exit(1);
             }
-            let filepath = &args[2];
-            let input = args[3].clone();
-            let turing = TuringMachine::from_file(filepath).input(input);
-            for tmove in turing {
-                println!("{}", tmove);
+        }
+        Command::Branch { entry, syms, machines, outpath } => {
+            let syms: Vec<String> = syms.split_whitespace().map(str::to_owned).collect();
+            let machines: Vec<String> = machines.split_whitespace().map(str::to_owned).collect();
+            if let Err(e) = turing::branch(&entry, &syms, &machines, &outpath) {
+                eprintln!("{}", e);
+                std::process::exit(1);
             }
         }
-        "chain" => {
-            if args.len() < 5 {
-                eprintln!("Expected 3 filepaths, but found {}.", args.len() - 2);
-                return;
+        Command::Loop { entry, loop_syms, outpath } => {
+            let loop_syms: Vec<String> = loop_syms.split_whitespace().map(str::to_owned).collect();
+            if let Err(e) = turing::loop_while(&entry, &loop_syms, &outpath) {
+                eprintln!("{}", e);
+                std::process::exit(1);
             }
-            let (m1, m2, out) = (args[2].as_str(), args[3].as_str(), args[4].as_str());
-            turing::chain(m1, m2, out);
         }
-        "branch" => {
-            if args.len() < 6 {
-                eprintln!("Too few arguments! Expected usage: \
-                    branch [ENTRYPOINT] [SYMS] [MACHINE_PATHS] [OUTPATH]");
-                return;
+        Command::Compile { machine, outpath } => {
+            if let Err(e) = turing::compile(&machine, &outpath) {
+                eprintln!("{}", e);
+                std::process::exit(1);
+            }
+        }
+        Command::Test { machine, max_steps } => {
+            run_test(machine, max_steps);
+        }
+        Command::Disasm { path } => {
+            match turing::bytecode::Bytecode::read_from(&path) {
+                Ok(code) => print!("{}", turing::bytecode::disassemble(&code)),
+                Err(e) => {
+                    eprintln!("Failed to read '{}': {}", path.display(), e);
+                    std::process::exit(1);
+                }
+            }
+        }
+        Command::Nondet { machine, input, max_depth, max_frontier } => {
+            match turing::run_nondeterministic(&machine, input, max_depth, max_frontier) {
+                Ok(outcome) => println!("{}", outcome),
+                Err(e) => {
+                    eprintln!("{}", e);
+                    std::process::exit(1);
+                }
             }
-            let entry = &args[2];
-            let syms: Vec<String> = args[3].split_whitespace()
-                .map(|s| s.to_owned()).collect();
-            let machines: Vec<String> = args[4].split_whitespace()
-                .map(|s| s.to_owned()).collect();
-            let outpath = &args[5];
-            turing::branch(entry, &syms, &machines, outpath)
-        }
-        "loop" => {
-            if args.len() < 5 {
-                eprintln!("Too few arguments! Expected usage: \
-                    loop [ENTRYPOINT] [SYMS] [OUTPATH]");
+        }
+    }
+}
+
+fn run_compute(machine: PathBuf, input: String, max_steps: Option<usize>, quiet: bool, tape_width: i64, detect_loops: bool, compiled: bool) {
+    let turing = if compiled { TuringMachine::from_bytecode(&machine) } else { TuringMachine::from_file(&machine) }
+        .and_then(|turing| turing.tape_width(tape_width).input(input));
+    let turing = match turing {
+        Ok(turing) => if detect_loops { turing.detect_loops() } else { turing },
+        Err(e) => {
+            eprintln!("{}", e);
+            std::process::exit(1);
+        }
+    };
+
+    let mut last = None;
+    for (step, tmove) in turing.enumerate() {
+        if let Some(limit) = max_steps {
+            if step >= limit {
+                println!("DID NOT HALT (exceeded {} steps)", limit);
                 return;
             }
-            let entry = &args[2];
-            let loop_syms: Vec<String> = args[3].split_whitespace()
-                .map(|s| s.to_owned()).collect();
-            let outpath = &args[4];
-            turing::loop_while(entry, &loop_syms, outpath);
         }
-        _ => eprintln!("Error: '{}' is not a valid command.", args[1]),
+        if !quiet {
+            println!("{}", tmove);
+        }
+        last = Some(tmove);
+    }
+    if quiet {
+        if let Some(tmove) = last {
+            println!("{}", tmove);
+        }
+    }
+}
+
+fn run_test(machine: PathBuf, max_steps: usize) {
+    let cases = match turing::testcase::load_cases(&machine) {
+        Ok(cases) => cases,
+        Err(e) => {
+            eprintln!("Failed to load test cases for '{}': {}", machine.display(), e);
+            std::process::exit(1);
+        }
+    };
+    if cases.is_empty() {
+        println!("No test cases found for '{}'.", machine.display());
+        return;
+    }
+
+    let mut failures = Vec::new();
+    for case in &cases {
+        match turing::testcase::run_case(&machine, case, max_steps) {
+            Ok(result) => if !result.passed() {
+                failures.push(result);
+            },
+            Err(e) => {
+                eprintln!("{}", e);
+                std::process::exit(1);
+            }
+        }
+    }
+
+    println!("{}/{} cases passed", cases.len() - failures.len(), cases.len());
+    if !failures.is_empty() {
+        println!();
+        for failure in &failures {
+            println!(
+                "FAIL: '{}' expected {} but got {}",
+                failure.case.input, failure.case.expect, failure.actual,
+            );
+        }
+        std::process::exit(1);
     }
-    
 }
@@ -1,9 +1,12 @@
-use std::{collections::{HashMap, HashSet}, fmt::{Write as _}, fs::File, io::{Read, Write as _}, ops::RangeBounds, path::Path};
+use std::{collections::{HashMap, HashSet, VecDeque}, fmt::{self, Write as _}, fs::File, io::{self, Read, Write as _}, ops::RangeBounds, path::Path};
 
-use fasthash::spooky::Hash128;
+use fasthash::{FastHash, spooky::Hash128};
 use rand::{Rng, distributions::Uniform};
 use regex::{Captures, Regex};
 
+pub mod bytecode;
+pub mod testcase;
+
 const WILDCARD:         &'static str = "*";
 const NO_STATE_CHANGE:  &'static str = ".";
 const NO_WRITE:         &'static str = ".";
@@ -11,18 +14,148 @@ const BLANK:            &'static str = "_";
 const STATE_DELIMITER:  &'static str = r"([,\-\s])";
 const BLANK_ID: usize = 0;
 
+/// A location in a machine source file, used to point diagnostics at the offending token.
+#[derive(Debug, Clone, PartialEq, Eq)]
+pub struct Location {
+    pub line: usize,
+    pub column: usize,
+    pub line_text: String,
+}
+
+/// Finds `token` within `haystack` (which `token` must be a literal substring slice of) and
+/// reports its line, column, and the full text of that line.
+fn locate(haystack: &str, token: &str) -> Location {
+    let offset = token.as_ptr() as usize - haystack.as_ptr() as usize;
+    let mut line = 1;
+    let mut line_start = 0;
+    for (i, b) in haystack.bytes().enumerate() {
+        if i >= offset {
+            break;
+        }
+        if b == b'\n' {
+            line += 1;
+            line_start = i + 1;
+        }
+    }
+    let line_text = haystack[line_start..].lines().next().unwrap_or("").to_owned();
+    let column = offset - line_start + 1;
+    Location { line, column, line_text }
+}
+
+/// An error encountered while parsing a machine source file, carrying the [`Location`] of
+/// the offending token so it can be reported back to the user instead of panicking.
+#[derive(Debug)]
+pub enum ParseError {
+    UndefinedState { name: String, loc: Location },
+    UndefinedSymbol { name: String, loc: Location },
+    ReversedRange { first: String, second: String, loc: Location },
+    WrongArity { found: usize, expected: usize, loc: Location },
+    CommandAfterTable { command: String, loc: Location },
+    MissingInitstate { loc: Location },
+    /// The machine file (or a sibling path, e.g. a `chain`/`branch` component) couldn't be
+    /// opened or read.
+    Io { path: String, source: io::Error },
+    /// The file has no `table` command at all, so there's nothing to locate a diagnostic in.
+    MissingTable { path: String },
+    /// An input tape (passed to [`TuringMachine::input`] or [`run_nondeterministic`]) used a
+    /// symbol that isn't one of the machine's defined `syms`. Has no source location, since
+    /// the offending token came from the input tape, not the machine file.
+    UnknownInputSymbol { token: String },
+}
+
+impl ParseError {
+    fn loc(&self) -> Option<&Location> {
+        match self {
+            Self::UndefinedState { loc, .. }
+            | Self::UndefinedSymbol { loc, .. }
+            | Self::ReversedRange { loc, .. }
+            | Self::WrongArity { loc, .. }
+            | Self::CommandAfterTable { loc, .. }
+            | Self::MissingInitstate { loc } => Some(loc),
+            Self::Io { .. } | Self::MissingTable { .. } | Self::UnknownInputSymbol { .. } => None,
+        }
+    }
+}
+
+impl fmt::Display for ParseError {
+    fn fmt(&self, f: &mut fmt::Formatter<'_>) -> fmt::Result {
+        let message = match self {
+            Self::UndefinedState { name, .. } =>
+                format!("state '{}' has not been defined", name),
+            Self::UndefinedSymbol { name, .. } =>
+                format!("symbol '{}' has not been defined", name),
+            Self::ReversedRange { first, second, .. } =>
+                format!("range '{}-{}' is reversed: '{}' was defined before '{}'", first, second, second, first),
+            Self::WrongArity { found, expected, .. } =>
+                format!("transition table entry had {} tokens, expected {}", found, expected),
+            Self::CommandAfterTable { command, .. } =>
+                format!("'{}' command must appear before the 'table' command", command),
+            Self::MissingInitstate { .. } =>
+                "'initstate' command is missing its state name".to_string(),
+            Self::Io { path, source } =>
+                return write!(f, "error: failed to read '{}': {}", path, source),
+            Self::MissingTable { path } =>
+                return write!(f, "error: '{}' does not define a 'table' command", path),
+            Self::UnknownInputSymbol { token } =>
+                return write!(f, "error: input contains the symbol '{}', which is not defined", token),
+        };
+        let loc = self.loc().expect("variants reaching here always carry a location");
+        writeln!(f, "error: {}", message)?;
+        writeln!(f, "  --> line {}, column {}", loc.line, loc.column)?;
+        writeln!(f, "   |")?;
+        writeln!(f, "{:>3} | {}", loc.line, loc.line_text)?;
+        write!(f, "   | {}^", " ".repeat(loc.column.saturating_sub(1)))
+    }
+}
+
+impl std::error::Error for ParseError {}
+
 pub struct TuringMachine {
     tape: HashMap<i64, usize, Hash128>,
     state: usize,
     pointer: i64,
     rules: TuringRules,
     done: bool,
+    tape_width: i64,
+    loop_detection: bool,
+    // 128-bit fingerprints of configurations (state, head position, nonblank tape cells)
+    // already visited, used by `loop_detection` to recognize a machine that will never
+    // halt. Storing just the fingerprint instead of the full configuration bounds memory
+    // use to one `u128` per visited configuration, regardless of tape size.
+    seen_configs: HashSet<u128, Hash128>,
+}
+
+/// Hashes a machine configuration (current state, head position, and the sorted nonblank
+/// tape cells) down to a 128-bit fingerprint, so [`TuringMachine::detect_loops`] can
+/// recognize a repeated configuration without keeping the full tape around.
+fn fingerprint_config(state: usize, pointer: i64, cells: &[(i64, usize)]) -> u128 {
+    let mut bytes = Vec::with_capacity(16 + cells.len() * 16);
+    bytes.extend_from_slice(&(state as u64).to_le_bytes());
+    bytes.extend_from_slice(&pointer.to_le_bytes());
+    for &(pos, sym) in cells {
+        bytes.extend_from_slice(&pos.to_le_bytes());
+        bytes.extend_from_slice(&(sym as u64).to_le_bytes());
+    }
+    Hash128::hash(&bytes)
 }
 
 impl TuringMachine {
 
-    pub fn from_file<P: AsRef<Path>>(filepath: P) -> Self {
-        let rules = TuringRules::parse_file(filepath);
+    pub fn from_file<P: AsRef<Path>>(filepath: P) -> Result<Self, ParseError> {
+        let rules = TuringRules::parse_file(filepath)?;
+        Ok(Self::with_rules(rules))
+    }
+
+    /// Loads a machine previously written out by [`compile`] instead of parsing source,
+    /// so it can be rerun without paying the parse cost again.
+    pub fn from_bytecode<P: AsRef<Path>>(filepath: P) -> Result<Self, ParseError> {
+        let path = filepath.as_ref().display().to_string();
+        let code = bytecode::Bytecode::read_from(filepath)
+            .map_err(|source| ParseError::Io { path, source })?;
+        Ok(Self::with_rules(TuringRules::from_bytecode(code)))
+    }
+
+    fn with_rules(rules: TuringRules) -> Self {
         let pointer = 0;
         let tape = HashMap::with_hasher(Hash128);
         let state = rules.initial_state;
@@ -32,23 +165,50 @@ impl TuringMachine {
             pointer,
             rules,
             done: false,
+            tape_width: 80,
+            loop_detection: false,
+            seen_configs: HashSet::with_hasher(Hash128),
         }
     }
 
-    pub fn input(mut self, tape: String) -> Self {
+    /// Writes `tape`'s whitespace-separated symbols onto the tape ending at the head.
+    /// Fails if `tape` uses a symbol the machine doesn't define.
+    pub fn input(mut self, tape: String) -> Result<Self, ParseError> {
         for (i, token) in tape.split_whitespace().rev().enumerate() {
-            self.tape.insert(-(i as i64), *self.rules.sym2idx.get(token)
-                .expect("Input string contained symbols which were not defined.")
-            );
+            let sym = *self.rules.sym2idx.get(token)
+                .ok_or_else(|| ParseError::UnknownInputSymbol { token: token.to_owned() })?;
+            self.tape.insert(-(i as i64), sym);
         }
+        Ok(self)
+    }
+
+    /// Sets the width of the tape window printed by [`get_string`](Self::get_string).
+    /// Defaults to `80`.
+    pub fn tape_width(mut self, width: i64) -> Self {
+        self.tape_width = width;
+        self
+    }
+
+    /// Enables loop detection: after each step, the full configuration (nonblank tape
+    /// cells, state and head position) is fingerprinted and checked against every
+    /// configuration seen so far. If one repeats, the machine can never halt, so iteration
+    /// stops with a "LOOPS (nonhalting)" verdict instead of running forever. Off by
+    /// default, so that exact traces of (believed) non-halting machines can still be
+    /// reproduced, and only pays the fingerprinting cost when asked for.
+    pub fn detect_loops(mut self) -> Self {
+        self.loop_detection = true;
         self
     }
 
     pub fn get_string(&self) -> String {
-        let width = 80;
-        let pointer = (self.pointer + width / 2) as usize + 1;
-        let mut string = format!("{:>left$}\n", "v", left=pointer);
-        for i in -width/2..width/2 {
+        let width = self.tape_width;
+        let half = width / 2;
+        // The head can wander outside the printed window (a non-halting left/right mover
+        // does this constantly); clamp the marker to the window's edge instead of letting
+        // the column arithmetic below go negative or overflow.
+        let marker_col = (self.pointer + half).clamp(0, width.saturating_sub(1));
+        let mut string = format!("{:>left$}\n", "v", left = marker_col as usize + 1);
+        for i in -half..half {
             if let Some(idx) = self.tape.get(&i) {
                 string.write_str(
                     self.rules.idx2sym.get(idx)
@@ -80,6 +240,18 @@ impl Iterator for TuringMachine {
                 Direction::Right => self.pointer += 1,
                 _ => {},
             }
+            if self.loop_detection {
+                let mut cells: Vec<(i64, usize)> = self.tape.iter()
+                    .filter(|&(_, &sym)| sym != BLANK_ID)
+                    .map(|(&pos, &sym)| (pos, sym))
+                    .collect();
+                cells.sort_unstable();
+                let fingerprint = fingerprint_config(self.state, self.pointer, &cells);
+                if !self.seen_configs.insert(fingerprint) {
+                    tape.push_str("\n    LOOPS (nonhalting)");
+                    done = true;
+                }
+            }
         } else {
             if self.state == 0 {
                 tape.push_str("\n    ACCEPTED");
@@ -97,6 +269,10 @@ impl Iterator for TuringMachine {
     }
 }
 
+/// Every alternative instruction defined for a `(state, symbol)` pair, in source order, used
+/// by the nondeterministic evaluator in `run_nondeterministic`.
+type NdTable = HashMap<(usize, usize), Vec<(usize, usize, Direction)>, Hash128>;
+
 /// A turing machine halts if there is no instruction for the state and read combination.
 /// We make symbols (String) into indices (usize)
 struct TuringRules {
@@ -105,13 +281,20 @@ struct TuringRules {
     // The set of states which are 'accepted' if the machine halts in them
     pub idx2sym: HashMap<usize, String, Hash128>,
     pub sym2idx: HashMap<String, usize, Hash128>,
-    // A hashmap describing what to do when you are in state s with head read r for (s, r).
-    // Format for output is (symbol to be written, direction to move the head, state to transition to)
-    pub transition_map: HashMap<(usize, usize), (usize, usize, Direction), Hash128>,
+    // Number of distinct states (including HALT) and symbols (including blank), used to
+    // index `table`.
+    pub(crate) num_states: usize,
+    pub(crate) num_syms: usize,
+    // The transition table compiled down to a flat, densely-indexed array: entry
+    // `state * num_syms + sym` holds the instruction to run when in `state` reading `sym`,
+    // so a step is a single array index instead of a hash lookup.
+    pub(crate) table: Vec<Option<Instr>>,
+    // Unlike `table`, which keeps only the first rule written for a pair, this keeps them all.
+    nd_table: NdTable,
 }
 
 #[derive(Clone, Copy, Debug)]
-enum Direction {
+pub(crate) enum Direction {
     Left,
     Right,
     Stay,
@@ -126,26 +309,100 @@ impl Direction {
             _ => Self::Stay,
         }
     }
+
+    pub(crate) fn to_byte(self) -> u8 {
+        match self {
+            Self::Left => 0,
+            Self::Right => 1,
+            Self::Stay => 2,
+        }
+    }
+
+    pub(crate) fn from_byte(b: u8) -> Self {
+        match b {
+            0 => Self::Left,
+            1 => Self::Right,
+            _ => Self::Stay,
+        }
+    }
+}
+
+/// A single compiled transition: what to write, which direction to move the head, and
+/// which state to transition to.
+#[derive(Clone, Copy, Debug)]
+pub(crate) struct Instr {
+    pub next_state: usize,
+    pub write: usize,
+    pub dir: Direction,
+}
+
+/// Lowers a sparse `(state, symbol) -> instruction` map into the dense, directly-indexable
+/// table used by [`TuringRules::get_move`] and written out by the `.tmc` bytecode format.
+fn compile_table(
+    transition_map: &HashMap<(usize, usize), (usize, usize, Direction), Hash128>,
+    num_states: usize,
+    num_syms: usize,
+) -> Vec<Option<Instr>> {
+    let mut table = vec![None; num_states * num_syms];
+    for (&(state, sym), &(next_state, write, dir)) in transition_map {
+        table[state * num_syms + sym] = Some(Instr { next_state, write, dir });
+    }
+    table
 }
 
 impl TuringRules {
 
     fn get_move(&self, st: usize, sym: usize) -> Option<(usize, usize, Direction)> {
-        if let Some(next_move) = self.transition_map.get(&(st, sym)) {
-            Some(*next_move)
-        } else {
-            None
+        self.table.get(st * self.num_syms + sym)
+            .copied()
+            .flatten()
+            .map(|instr| (instr.next_state, instr.write, instr.dir))
+    }
+
+    /// All alternative moves defined for `(st, sym)`, for the nondeterministic evaluator.
+    fn get_moves(&self, st: usize, sym: usize) -> &[(usize, usize, Direction)] {
+        self.nd_table.get(&(st, sym)).map(Vec::as_slice).unwrap_or(&[])
+    }
+
+    /// Rebuilds rules from a loaded [`bytecode::Bytecode`]. The `.tmc` format only carries
+    /// the dense, first-rule-wins table, not every alternative instruction, so a machine
+    /// loaded this way has an empty `nd_table` and can't be run with `run_nondeterministic`.
+    fn from_bytecode(code: bytecode::Bytecode) -> Self {
+        let mut idx2sym = HashMap::with_hasher(Hash128);
+        let mut sym2idx = HashMap::with_hasher(Hash128);
+        for (i, sym) in code.syms.into_iter().enumerate() {
+            sym2idx.insert(sym.clone(), i);
+            idx2sym.insert(i, sym);
+        }
+        Self {
+            initial_state: code.initial_state,
+            idx2sym,
+            sym2idx,
+            num_states: code.num_states,
+            num_syms: code.num_syms,
+            table: code.instrs,
+            nd_table: HashMap::with_hasher(Hash128),
         }
     }
 
-    fn parse_file<P: AsRef<Path>>(path: P) -> Self {
+    fn parse_file<P: AsRef<Path>>(path: P) -> Result<Self, ParseError> {
 
         let (
-            state_names, 
-            sym_names, 
-            initial_state_name, 
+            state_names,
+            sym_names,
+            initial_state_name,
+            initial_state_loc,
             table,
-        ) = extract_tokens(&path);
+            text,
+        ) = extract_tokens(&path)?;
+
+        // The untouched table text, still a literal slice of `text` -- unlike `table` above,
+        // whose state names have been rewritten with the disambiguation prefix and so can no
+        // longer be located against the source file. Used only so `locate()` below reports
+        // the line/column the user actually wrote, rather than the rewritten one.
+        let raw_table = text.split_once("table")
+            .map(|(_, tbl)| tbl.trim_start().trim_end())
+            .unwrap_or_default();
 
         // State parsing starts here
         let mut state2idx = HashMap::with_hasher(Hash128);
@@ -171,64 +428,79 @@ impl TuringRules {
 
         // Defining initial_state
         let initial_state = *state2idx.get(&initial_state_name)
-            .expect(&format!("Initial state '{}' was not defined \
-            with a 'states' command.", initial_state_name));
+            .ok_or_else(|| ParseError::UndefinedState {
+                name: initial_state_name.clone(),
+                loc: initial_state_loc.clone(),
+            })?;
 
         // Transition table parsing starts here!
         let mut transition_map = HashMap::with_hasher(Hash128);
+        let mut nd_table: NdTable = HashMap::with_hasher(Hash128);
 
-        for line in table.lines() {
+        for (line, raw_line) in table.lines().zip(raw_table.lines()) {
             if line.is_empty() {
                 continue;
             }
             let tokens: Vec<&str> = line.split_whitespace().collect();
+            let raw_tokens: Vec<&str> = raw_line.split_whitespace().collect();
             if tokens.len() != 5 {
-                panic!("Transition table had an entry with {} tokens, \
-                    while parser only allows entries with 5 tokens.", tokens.len());
+                return Err(ParseError::WrongArity {
+                    found: tokens.len(),
+                    expected: 5,
+                    loc: locate(&text, raw_line),
+                });
             }
-            
+
             let mut states = Vec::new();
-            for s in tokens[0].split(",") {
+            for (s, raw_s) in tokens[0].split(",").zip(raw_tokens[0].split(",")) {
                 let first;
                 let last;
                 if let Some((s1, s2)) = s.split_once("-") {
+                    let (raw_s1, raw_s2) = raw_s.split_once("-").unwrap_or((raw_s, raw_s));
                     first = *state2idx.get(s1)
-                        .expect(&format!("State '{}' has not been defined.", s1));
+                        .ok_or_else(|| ParseError::UndefinedState { name: s1.to_owned(), loc: locate(&text, raw_s1) })?;
                     last = *state2idx.get(s2)
-                        .expect(&format!("State '{}' has not been defined.", s2));
+                        .ok_or_else(|| ParseError::UndefinedState { name: s2.to_owned(), loc: locate(&text, raw_s2) })?;
                     if first > last {
-                        panic!("State '{}' was defined before state '{}', \
-                            did you put them in the wrong order?", s2, s1);
+                        return Err(ParseError::ReversedRange {
+                            first: s1.to_owned(),
+                            second: s2.to_owned(),
+                            loc: locate(&text, raw_s),
+                        });
                     }
                 } else {
                     first = *state2idx.get(s)
-                        .expect(&format!("State '{}' has not been defined", s));
+                        .ok_or_else(|| ParseError::UndefinedState { name: s.to_owned(), loc: locate(&text, raw_s) })?;
                     last = first;
                 }
                 for id in first..last + 1 {
                     states.push(id);
                 }
             }
-            
+
             let mut syms = Vec::new();
-            for s in tokens[1].split(",") {
+            for (s, raw_s) in tokens[1].split(",").zip(raw_tokens[1].split(",")) {
                 let first;
                 let last;
                 if let Some((sym1, sym2)) = s.split_once("-") {
+                    let (raw_sym1, raw_sym2) = raw_s.split_once("-").unwrap_or((raw_s, raw_s));
                     first = *sym2idx.get(sym1)
-                        .expect(&format!("Symbol '{}' has not been defined.", sym1));
+                        .ok_or_else(|| ParseError::UndefinedSymbol { name: sym1.to_owned(), loc: locate(&text, raw_sym1) })?;
                     last = *sym2idx.get(sym2)
-                        .expect(&format!("Symbol '{}' has not been defined.", sym2));
+                        .ok_or_else(|| ParseError::UndefinedSymbol { name: sym2.to_owned(), loc: locate(&text, raw_sym2) })?;
                     if first > last {
-                        panic!("Symbol '{}' was defined before symbol '{}', \
-                        did you put them in the wrong order?", sym2, sym1);
+                        return Err(ParseError::ReversedRange {
+                            first: sym1.to_owned(),
+                            second: sym2.to_owned(),
+                            loc: locate(&text, raw_s),
+                        });
                     }
                 } else if tokens[1] == WILDCARD {
                     first = 0;
                     last = sym2idx.len() - 1;
                 } else {
                     first = *sym2idx.get(s)
-                        .expect(&format!("Symbol '{}' has not been defined.", s));
+                        .ok_or_else(|| ParseError::UndefinedSymbol { name: s.to_owned(), loc: locate(&text, raw_s) })?;
                     last = first;
                 }
                 for idx in first..last + 1 {
@@ -240,62 +512,203 @@ impl TuringRules {
                 None
             } else {
                 Some(*state2idx.get(tokens[2])
-                    .expect(&format!("State '{}' has not been defined.", tokens[2])))
+                    .ok_or_else(|| ParseError::UndefinedState { name: tokens[2].to_owned(), loc: locate(&text, raw_tokens[2]) })?)
             };
             let write =  if tokens[3].ends_with(NO_WRITE) {
                 None
             } else {
                 Some(*sym2idx.get(tokens[3])
-                    .expect(&format!("Symbol '{}' has not been defined.", tokens[3])))
+                    .ok_or_else(|| ParseError::UndefinedSymbol { name: tokens[3].to_owned(), loc: locate(&text, raw_tokens[3]) })?)
             };
             let d = Direction::from(tokens[4]);
             for state_idx in &states {
                 for sym_idx in &syms {
-                    if let None = transition_map.get(&(*state_idx, *sym_idx)) {
-                        let ns = if let Some(ns) = new_state {
-                            ns
-                        } else {
-                            *state_idx
-                        };
-                        let w = if let Some(w) = write {
-                            w
-                        } else {
-                            *sym_idx
-                        };
-                        transition_map.insert((*state_idx, *sym_idx), (ns, w, d));
-                    }
+                    let ns = if let Some(ns) = new_state {
+                        ns
+                    } else {
+                        *state_idx
+                    };
+                    let w = if let Some(w) = write {
+                        w
+                    } else {
+                        *sym_idx
+                    };
+                    // `nd_table` keeps every alternative for a `(state, symbol)` pair, so
+                    // the nondeterministic evaluator can explore all of them, even though
+                    // only the first one written wins in the deterministic `transition_map`.
+                    nd_table.entry((*state_idx, *sym_idx)).or_default().push((ns, w, d));
+                    transition_map.entry((*state_idx, *sym_idx)).or_insert((ns, w, d));
                 }
             }
         }
 
-        Self {
+        let num_states = state2idx.len();
+        let num_syms = sym2idx.len();
+        let table = compile_table(&transition_map, num_states, num_syms);
+
+        Ok(Self {
             initial_state,
             idx2sym,
             sym2idx,
-            transition_map,
+            num_states,
+            num_syms,
+            table,
+            nd_table,
+        })
+    }
+}
+
+/// Parses the machine at `machine_path` and writes its compiled, dense transition table
+/// out to `outpath` as `.tmc` bytecode, so it can be loaded and run again without
+/// re-parsing (or disassembled with [`bytecode::disassemble`]).
+pub fn compile<P: AsRef<Path>, L: AsRef<Path>>(machine_path: P, outpath: L) -> Result<(), ParseError> {
+    let rules = TuringRules::parse_file(machine_path)?;
+    let syms = (0..rules.num_syms)
+        .map(|i| rules.idx2sym.get(&i).cloned().unwrap_or_default())
+        .collect();
+    let code = bytecode::Bytecode {
+        num_states: rules.num_states,
+        num_syms: rules.num_syms,
+        initial_state: rules.initial_state,
+        syms,
+        instrs: rules.table,
+    };
+    code.write_to(outpath).expect("Failed when writing bytecode file.");
+    Ok(())
+}
+
+/// A tape represented as its sorted, nonblank cells, used by [`run_nondeterministic`] so a
+/// whole configuration can be cloned cheaply per branch instead of carrying a `HashMap`.
+type SparseTape = Vec<(i64, usize)>;
+
+fn sparse_get(tape: &SparseTape, pos: i64) -> usize {
+    tape.binary_search_by_key(&pos, |&(p, _)| p)
+        .map(|i| tape[i].1)
+        .unwrap_or(BLANK_ID)
+}
+
+fn sparse_set(tape: &mut SparseTape, pos: i64, sym: usize) {
+    match tape.binary_search_by_key(&pos, |&(p, _)| p) {
+        Ok(i) if sym == BLANK_ID => { tape.remove(i); },
+        Ok(i) => tape[i].1 = sym,
+        Err(i) if sym != BLANK_ID => tape.insert(i, (pos, sym)),
+        Err(_) => {},
+    }
+}
+
+/// The verdict reached by [`run_nondeterministic`].
+#[derive(Debug, Clone, Copy, PartialEq, Eq)]
+pub enum NdOutcome {
+    /// Some computation path reached the halting state.
+    Accepted,
+    /// Every computation path ran out of moves without reaching the halting state.
+    Rejected,
+    /// The search exhausted `max_depth` or `max_frontier` before settling either way.
+    Unknown,
+}
+
+impl fmt::Display for NdOutcome {
+    fn fmt(&self, f: &mut fmt::Formatter<'_>) -> fmt::Result {
+        match self {
+            Self::Accepted => write!(f, "ACCEPTED"),
+            Self::Rejected => write!(f, "REJECTED"),
+            Self::Unknown => write!(f, "UNKNOWN (exceeded depth/frontier bound)"),
         }
     }
 }
 
-/// Makes a new machine at `outpath`, which takes the output from the 
+/// Simulates a (possibly nondeterministic) machine by exploring every applicable rule at
+/// each `(state, symbol)` instead of only the first one defined for it. Configurations are
+/// explored breadth-first, one step per frontier at a time, and deduplicated by fingerprint
+/// (state, head position, nonblank tape cells) to prune the exponential blowup; the search
+/// accepts as soon as any branch reaches the halting state, the same way
+/// [`TuringMachine`]'s `Iterator` impl does for the deterministic case. Bounded by
+/// `max_depth` (steps) and `max_frontier` (configurations alive at once) since an
+/// unconstrained nondeterministic search can blow up or never terminate.
+pub fn run_nondeterministic<P: AsRef<Path>>(
+    machine_path: P,
+    input: String,
+    max_depth: usize,
+    max_frontier: usize,
+) -> Result<NdOutcome, ParseError> {
+    let rules = TuringRules::parse_file(machine_path)?;
+
+    let mut tape: SparseTape = Vec::new();
+    for (i, token) in input.split_whitespace().rev().enumerate() {
+        let sym = *rules.sym2idx.get(token)
+            .ok_or_else(|| ParseError::UnknownInputSymbol { token: token.to_owned() })?;
+        sparse_set(&mut tape, -(i as i64), sym);
+    }
+
+    let mut seen: HashSet<u128, Hash128> = HashSet::with_hasher(Hash128);
+    seen.insert(fingerprint_config(rules.initial_state, 0, &tape));
+    let mut frontier = VecDeque::from([(rules.initial_state, 0i64, tape)]);
+
+    for _ in 0..max_depth {
+        if frontier.is_empty() {
+            return Ok(NdOutcome::Rejected);
+        }
+        if frontier.len() > max_frontier {
+            return Ok(NdOutcome::Unknown);
+        }
+
+        let mut next_frontier = VecDeque::new();
+        for (state, pointer, tape) in frontier {
+            if state == 0 {
+                return Ok(NdOutcome::Accepted);
+            }
+            let sym = sparse_get(&tape, pointer);
+            for &(next_state, write, dir) in rules.get_moves(state, sym) {
+                let mut tape = tape.clone();
+                sparse_set(&mut tape, pointer, write);
+                let next_pointer = match dir {
+                    Direction::Left  => pointer - 1,
+                    Direction::Right => pointer + 1,
+                    Direction::Stay  => pointer,
+                };
+                if seen.insert(fingerprint_config(next_state, next_pointer, &tape)) {
+                    next_frontier.push_back((next_state, next_pointer, tape));
+                }
+            }
+        }
+        frontier = next_frontier;
+    }
+
+    if frontier.iter().any(|&(state, _, _)| state == 0) {
+        Ok(NdOutcome::Accepted)
+    } else if frontier.is_empty() {
+        Ok(NdOutcome::Rejected)
+    } else {
+        Ok(NdOutcome::Unknown)
+    }
+}
+
+/// Runs [`extract_tokens`] and discards the location/source-text fields the
+/// machine-composition functions below don't need.
+fn expect_tokens<P: AsRef<Path>>(path: P) -> Result<(Vec<String>, Vec<String>, String, String), ParseError> {
+    let (states, syms, init_state, _loc, table, _text) = extract_tokens(path)?;
+    Ok((states, syms, init_state, table))
+}
+
+/// Makes a new machine at `outpath`, which takes the output from the
 /// machine at `machine1` as input to the machine at `machine2`.
-pub fn chain<P, T, L>(machine1: P, machines: &[T], outpath: L) 
+pub fn chain<P, T, L>(machine1: P, machines: &[T], outpath: L) -> Result<(), ParseError>
     where P: AsRef<Path>, T: AsRef<Path>, L: AsRef<Path>
 {
     let (
-        mut states, 
-        mut syms, 
-        init_state, 
+        mut states,
+        mut syms,
+        init_state,
         mut table,
-    ) = extract_tokens(&machine1);
+    ) = expect_tokens(&machine1)?;
 
     for path in machines {
         let (
-            states2, 
-            syms2, 
-            init_state2, 
+            states2,
+            syms2,
+            init_state2,
             table2,
-        ) = extract_tokens(&path);
+        ) = expect_tokens(&path)?;
 
         for state in states2 {
             states.push(state);
@@ -313,21 +726,22 @@ pub fn chain<P, T, L>(machine1: P, machines: &[T], outpath: L)
     }
 
     write_to_file(outpath, states, syms, init_state, table);
+    Ok(())
 }
 
 pub fn branch<P, T, L>(
-    entry: P, 
-    branch_syms: &[String], 
+    entry: P,
+    branch_syms: &[String],
     machines: &[T],
     outpath: L
-) where P: AsRef<Path>, T: AsRef<Path>, L: AsRef<Path> {
+) -> Result<(), ParseError> where P: AsRef<Path>, T: AsRef<Path>, L: AsRef<Path> {
 
     let (
-        mut states, 
-        mut syms, 
-        init_state, 
+        mut states,
+        mut syms,
+        init_state,
         mut table,
-    ) = extract_tokens(&entry);
+    ) = expect_tokens(&entry)?;
 
     table = table.replace("HALT", "BRANCH");
     states.push("BRANCH".to_string());
@@ -346,7 +760,7 @@ pub fn branch<P, T, L>(
             cur_syms,
             cur_init_state,
             cur_table,
-        ) = extract_tokens(path);
+        ) = expect_tokens(path)?;
 
         for state in cur_states {
             states.push(state);
@@ -367,19 +781,20 @@ pub fn branch<P, T, L>(
     }
 
     write_to_file(outpath, states, syms, init_state, table);
+    Ok(())
 }
 
 pub fn loop_while<P: AsRef<Path>, L: AsRef<Path>>(
-    entry: P, 
+    entry: P,
     loop_syms: &[String],
     outpath: L
-) {
+) -> Result<(), ParseError> {
     let (
-        mut states, 
-        mut syms, 
-        init_state, 
+        mut states,
+        mut syms,
+        init_state,
         table,
-    ) = extract_tokens(&entry);
+    ) = expect_tokens(&entry)?;
 
     states.push("CHECK".to_owned());
 
@@ -391,9 +806,10 @@ pub fn loop_while<P: AsRef<Path>, L: AsRef<Path>>(
 
     let mut table = table.replace("HALT", "CHECK");
     table.push_str(&format!("\nCHECK {} {} . N", loop_syms.join(","), init_state));
-    table.push_str(&format!("\nCHECK * HALT . N"));
+    table.push_str("\nCHECK * HALT . N");
 
-    write_to_file(outpath, states, syms, init_state, table)
+    write_to_file(outpath, states, syms, init_state, table);
+    Ok(())
 }
 
 fn write_to_file<P: AsRef<Path>>(
@@ -431,47 +847,53 @@ fn write_to_file<P: AsRef<Path>>(
     println!("Wrote machine to '{}'.", path.as_ref().to_str().unwrap());
 }
 
-/// Returns tokens in the order:
+/// The tokens returned by [`extract_tokens`], in order:
 /// - states
 /// - symbols
 /// - initial state
+/// - initial state's location in the source, for diagnostics
 /// - table
-fn extract_tokens<P: AsRef<Path>>(path: P) -> (
-    Vec<String>,
-    Vec<String>,
-    String,
-    String,
-) {
+/// - the untouched source text, so callers can locate diagnostics against what the user
+///   actually wrote instead of the renamed/reassembled `table` above
+type ExtractedTokens = (Vec<String>, Vec<String>, String, Location, String, String);
+
+fn extract_tokens<P: AsRef<Path>>(path: P) -> Result<ExtractedTokens, ParseError> {
+    let path_display = path.as_ref().display().to_string();
     let mut text = String::new();
     File::open(&path)
-        .expect(&format!("Failed when opening file at '{}'.", 
-        path.as_ref().to_str().expect("Failed to parse path as string.")))
-        .read_to_string(&mut text)
-        .expect("Failed when reading file to string.");
+        .and_then(|mut file| file.read_to_string(&mut text))
+        .map_err(|source| ParseError::Io { path: path_display.clone(), source })?;
 
     let mut prefix = String::from("");
     let mut rng = rand::thread_rng();
     for _ in 0..16 {
         prefix.push_str(&rng.gen_range(0..10).to_string());
     }
-    
-    let (commands, mut table) = if let Some((cmds, tbl)) = text.split_once("table") {
-        // Panics if there are commands defined after the `table`-command
-        if tbl.contains("states")
-            || tbl.contains("syms")
-            || tbl.contains("initstate")
-            || tbl.contains("finalstates") 
-        {
-            panic!("All states and symbols must be defined before the 'table'-command.");
-        }
-        (cmds.to_owned(), format!("\n{}\n", tbl.trim_start().trim_end()))
-    } else {
-        panic!("Transition table was not defined!");
+
+    let (commands, mut table) = match text.split_once("table") {
+        Some((cmds, tbl)) => {
+            // Errors if there are commands defined after the `table`-command
+            for line in tbl.lines() {
+                let command = match line.split_whitespace().next() {
+                    Some(command) => command,
+                    None => continue,
+                };
+                if matches!(command, "states" | "syms" | "initstate" | "finalstates") {
+                    return Err(ParseError::CommandAfterTable {
+                        command: command.to_owned(),
+                        loc: locate(&text, command),
+                    });
+                }
+            }
+            (cmds, format!("\n{}\n", tbl.trim_start().trim_end()))
+        },
+        None => return Err(ParseError::MissingTable { path: path_display }),
     };
 
     let mut states = Vec::new();
     let mut syms = Vec::new();
     let mut initial_state = String::new();
+    let mut initial_state_loc = Location { line: 0, column: 0, line_text: String::new() };
 
     for line in commands.lines() {
         // Filters out code comments
@@ -502,14 +924,15 @@ fn extract_tokens<P: AsRef<Path>>(path: P) -> (
             },
             Some("initstate") => {
                 let state = tokens.next()
-                    .expect("Machine must have an initstate.");
+                    .ok_or_else(|| ParseError::MissingInitstate { loc: locate(&text, line) })?;
+                initial_state_loc = locate(&text, state);
                 initial_state = format!("{}::{}", prefix, state);
             }
             _ => {},
         }
     }
 
-    (states, syms, initial_state, table.trim_end().trim_start().to_owned())
+    Ok((states, syms, initial_state, initial_state_loc, table.trim_end().trim_start().to_owned(), text))
 }
 
 fn get_state_regex(state: &str) -> Regex {
@@ -0,0 +1,135 @@
+//! Binary `.tmc` bytecode format for a compiled [`super::TuringRules`] transition table.
+//!
+//! The format is a magic header, the state/symbol counts and initial state needed to decode
+//! the table, the symbol table (so input tapes and tape snapshots can still be rendered),
+//! then one fixed-width record per `(state, symbol)` pair:
+//!
+//! ```text
+//! magic:         4 bytes, b"TMC2"
+//! num_states:    u32 (little-endian)
+//! num_syms:      u32 (little-endian)
+//! initial_state: u32 (little-endian)
+//! syms:          num_syms entries of:
+//!                    len: u32 (little-endian)
+//!                    utf8 bytes of the symbol, `len` bytes long
+//! records:       num_states * num_syms entries of:
+//!                    present:    u8   (0 = no transition, 1 = transition follows)
+//!                    next_state: u32
+//!                    write:      u32
+//!                    dir:        u8   (0 = Left, 1 = Right, 2 = Stay)
+//! ```
+
+use std::{
+    fs::File,
+    io::{self, Read, Write},
+    path::Path,
+};
+
+use super::{Direction, Instr};
+
+const MAGIC: &[u8; 4] = b"TMC2";
+const RECORD_LEN: usize = 1 + 4 + 4 + 1;
+
+/// A compiled transition table, ready to be written to or read from a `.tmc` file.
+pub struct Bytecode {
+    pub(crate) num_states: usize,
+    pub(crate) num_syms: usize,
+    pub(crate) initial_state: usize,
+    // Symbol names in index order (`syms[i]` is the symbol with index `i`), so a loaded
+    // `Bytecode` carries enough to map an input tape's tokens to indices and back.
+    pub(crate) syms: Vec<String>,
+    pub(crate) instrs: Vec<Option<Instr>>,
+}
+
+impl Bytecode {
+    pub fn write_to<P: AsRef<Path>>(&self, path: P) -> io::Result<()> {
+        let mut out = File::create(path)?;
+        out.write_all(MAGIC)?;
+        out.write_all(&(self.num_states as u32).to_le_bytes())?;
+        out.write_all(&(self.num_syms as u32).to_le_bytes())?;
+        out.write_all(&(self.initial_state as u32).to_le_bytes())?;
+        for sym in &self.syms {
+            out.write_all(&(sym.len() as u32).to_le_bytes())?;
+            out.write_all(sym.as_bytes())?;
+        }
+        for instr in &self.instrs {
+            let mut record = [0u8; RECORD_LEN];
+            if let Some(instr) = instr {
+                record[0] = 1;
+                record[1..5].copy_from_slice(&(instr.next_state as u32).to_le_bytes());
+                record[5..9].copy_from_slice(&(instr.write as u32).to_le_bytes());
+                record[9] = instr.dir.to_byte();
+            }
+            out.write_all(&record)?;
+        }
+        Ok(())
+    }
+
+    pub fn read_from<P: AsRef<Path>>(path: P) -> io::Result<Self> {
+        let mut bytes = Vec::new();
+        File::open(path)?.read_to_end(&mut bytes)?;
+
+        if bytes.len() < 12 || &bytes[0..4] != MAGIC {
+            return Err(io::Error::new(io::ErrorKind::InvalidData, "not a valid .tmc file"));
+        }
+        let num_states = u32::from_le_bytes(bytes[4..8].try_into().unwrap()) as usize;
+        let num_syms = u32::from_le_bytes(bytes[8..12].try_into().unwrap()) as usize;
+        if bytes.len() < 16 {
+            return Err(io::Error::new(io::ErrorKind::InvalidData, "truncated .tmc file"));
+        }
+        let initial_state = u32::from_le_bytes(bytes[12..16].try_into().unwrap()) as usize;
+
+        let mut pos = 16;
+        let mut syms = Vec::with_capacity(num_syms);
+        for _ in 0..num_syms {
+            if pos + 4 > bytes.len() {
+                return Err(io::Error::new(io::ErrorKind::InvalidData, "truncated .tmc file"));
+            }
+            let len = u32::from_le_bytes(bytes[pos..pos + 4].try_into().unwrap()) as usize;
+            pos += 4;
+            if pos + len > bytes.len() {
+                return Err(io::Error::new(io::ErrorKind::InvalidData, "truncated .tmc file"));
+            }
+            let sym = String::from_utf8(bytes[pos..pos + len].to_vec())
+                .map_err(|e| io::Error::new(io::ErrorKind::InvalidData, e))?;
+            syms.push(sym);
+            pos += len;
+        }
+
+        let records = &bytes[pos..];
+        let expected_len = num_states * num_syms * RECORD_LEN;
+        if records.len() != expected_len {
+            return Err(io::Error::new(io::ErrorKind::InvalidData, "truncated .tmc file"));
+        }
+
+        let instrs = records.chunks_exact(RECORD_LEN).map(|record| {
+            if record[0] == 0 {
+                None
+            } else {
+                let next_state = u32::from_le_bytes(record[1..5].try_into().unwrap()) as usize;
+                let write = u32::from_le_bytes(record[5..9].try_into().unwrap()) as usize;
+                let dir = Direction::from_byte(record[9]);
+                Some(Instr { next_state, write, dir })
+            }
+        }).collect();
+
+        Ok(Self { num_states, num_syms, initial_state, syms, instrs })
+    }
+}
+
+/// Renders a compiled table as a human-readable transition listing: one line per defined
+/// `(state, symbol)` pair, in the form `q{state} read {sym} -> write {write}, move {dir}, goto q{next}`.
+pub fn disassemble(code: &Bytecode) -> String {
+    let mut out = String::new();
+    for state in 0..code.num_states {
+        for sym in 0..code.num_syms {
+            if let Some(instr) = code.instrs[state * code.num_syms + sym] {
+                out.push_str(&format!(
+                    "q{} read {} -> write {}, move {:?}, goto q{}\n",
+                    state, sym, instr.write, instr.dir, instr.next_state,
+                ));
+            }
+        }
+    }
+    out
+}
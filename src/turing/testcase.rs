@@ -0,0 +1,130 @@
+//! Embedded test cases for a machine: lines of the form `INPUT => ACCEPT` / `INPUT => REJECT`
+//! that declare what a machine should do on a given input tape. These can live directly in
+//! the machine file (e.g. as comments) or in a sibling `.test` file, and are what the `test`
+//! subcommand drives to make machines -- and the composition functions `chain`, `branch` and
+//! `loop_while` -- regression-testable instead of eyeballed.
+
+use std::{fmt, fs, io, path::Path};
+
+use super::TuringMachine;
+
+/// What a [`TestCase`] expects a machine to do with its input.
+#[derive(Debug, Clone, Copy, PartialEq, Eq)]
+pub enum Expectation {
+    Accept,
+    Reject,
+}
+
+impl fmt::Display for Expectation {
+    fn fmt(&self, f: &mut fmt::Formatter<'_>) -> fmt::Result {
+        match self {
+            Self::Accept => write!(f, "ACCEPT"),
+            Self::Reject => write!(f, "REJECT"),
+        }
+    }
+}
+
+/// What actually happened when a [`TestCase`] was run.
+#[derive(Debug, Clone, PartialEq, Eq)]
+pub enum Outcome {
+    Accept,
+    Reject,
+    DidNotHalt,
+    /// The case's input tape used a symbol the machine doesn't define.
+    InvalidInput(String),
+}
+
+impl fmt::Display for Outcome {
+    fn fmt(&self, f: &mut fmt::Formatter<'_>) -> fmt::Result {
+        match self {
+            Self::Accept => write!(f, "ACCEPT"),
+            Self::Reject => write!(f, "REJECT"),
+            Self::DidNotHalt => write!(f, "DID NOT HALT"),
+            Self::InvalidInput(reason) => write!(f, "INVALID INPUT ({})", reason),
+        }
+    }
+}
+
+/// One `INPUT => ACCEPT`/`INPUT => REJECT` declaration.
+#[derive(Debug, Clone)]
+pub struct TestCase {
+    pub input: String,
+    pub expect: Expectation,
+}
+
+/// The result of running a single [`TestCase`] against a machine.
+pub struct CaseResult {
+    pub case: TestCase,
+    pub actual: Outcome,
+}
+
+impl CaseResult {
+    pub fn passed(&self) -> bool {
+        matches!(
+            (self.case.expect, &self.actual),
+            (Expectation::Accept, Outcome::Accept) | (Expectation::Reject, Outcome::Reject)
+        )
+    }
+}
+
+/// Loads the test cases for `machine_path`: the sibling `.test` file if one exists,
+/// otherwise any `INPUT => ACCEPT`/`INPUT => REJECT` lines found in the machine file itself.
+pub fn load_cases<P: AsRef<Path>>(machine_path: P) -> io::Result<Vec<TestCase>> {
+    let machine_path = machine_path.as_ref();
+    let sibling = machine_path.with_extension("test");
+    let text = if sibling.is_file() {
+        fs::read_to_string(sibling)?
+    } else {
+        fs::read_to_string(machine_path)?
+    };
+    Ok(parse_cases(&text))
+}
+
+/// Parses every `INPUT => ACCEPT`/`INPUT => REJECT` line out of `text`, skipping anything
+/// that doesn't match (e.g. the machine's `states`/`syms`/`table` commands). A leading `#`
+/// is stripped first, so cases can be declared as comments directly in the machine file
+/// without the `#` ending up as part of the input.
+fn parse_cases(text: &str) -> Vec<TestCase> {
+    text.lines().filter_map(|line| {
+        let line = line.trim().strip_prefix('#').unwrap_or(line);
+        let (input, expect) = line.split_once("=>")?;
+        let expect = match expect.trim() {
+            "ACCEPT" => Expectation::Accept,
+            "REJECT" => Expectation::Reject,
+            _ => return None,
+        };
+        Some(TestCase { input: input.trim().to_owned(), expect })
+    }).collect()
+}
+
+/// Runs `case` against the machine at `machine_path`, driving it to completion (or up to
+/// `max_steps` tape snapshots, whichever comes first) and recording the outcome.
+pub fn run_case<P: AsRef<Path>>(
+    machine_path: P,
+    case: &TestCase,
+    max_steps: usize,
+) -> Result<CaseResult, super::ParseError> {
+    let turing = match TuringMachine::from_file(machine_path)?.input(case.input.clone()) {
+        Ok(turing) => turing,
+        Err(super::ParseError::UnknownInputSymbol { token }) => {
+            return Ok(CaseResult { case: case.clone(), actual: Outcome::InvalidInput(token) });
+        }
+        Err(e) => return Err(e),
+    };
+
+    let mut actual = Outcome::DidNotHalt;
+    for (step, tmove) in turing.enumerate() {
+        if step >= max_steps {
+            break;
+        }
+        if tmove.ends_with("ACCEPTED") {
+            actual = Outcome::Accept;
+            break;
+        } else if tmove.ends_with("REJECTED") {
+            actual = Outcome::Reject;
+            break;
+        }
+    }
+
+    Ok(CaseResult { case: case.clone(), actual })
+}